@@ -1,7 +1,10 @@
-use egg::{Id, Language};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use egg::{CostFunction, Id, Language, RecExpr};
 
 use crate::{
-    cad::Cad,
+    cad::{Cad, EGraph},
     hyperparameters::{COST_BIG_VALUE, COST_SMALL_VALUE},
 };
 
@@ -53,3 +56,195 @@ impl egg::CostFunction<Cad> for CostFn {
         enode.fold(cost, |sum, i| sum + costs(i))
     }
 }
+
+// Costs of the cheapest term of every e-class, computed to a fixpoint as egg's Extractor does
+fn class_costs(egraph: &EGraph) -> HashMap<Id, Cost> {
+    let mut costs = HashMap::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for class in egraph.classes() {
+            let best = class
+                .nodes
+                .iter()
+                .filter_map(|n| node_cost(egraph, &costs, n))
+                .fold(None, |best: Option<Cost>, c| {
+                    Some(best.map_or(c, |b| b.min(c)))
+                });
+            if let Some(c) = best {
+                let improves = match costs.get(&class.id) {
+                    Some(&old) => c < old,
+                    None => true,
+                };
+                if improves {
+                    costs.insert(class.id, c);
+                    changed = true;
+                }
+            }
+        }
+    }
+    costs
+}
+
+fn node_cost(egraph: &EGraph, costs: &HashMap<Id, Cost>, enode: &Cad) -> Option<Cost> {
+    if enode.all(|c| costs.contains_key(&egraph.find(c))) {
+        Some(CostFn.cost(enode, |c| costs[&egraph.find(c)]))
+    } else {
+        None
+    }
+}
+
+// Summing the same costs in a different order (e.g. commuted operands) may differ in the last bits
+fn is_tie(a: Cost, b: Cost) -> bool {
+    (a - b).abs() <= a.abs().max(b.abs()) * 1e-9
+}
+
+// Picks, in every e-class, the cheapest e-node, and among equally cheap ones the structurally
+// smallest term: e-nodes are compared by operator (with its payload) first, then by the terms
+// chosen for their children. Children are strictly cheaper than their parent, so this terminates.
+struct TieBreak<'a> {
+    egraph: &'a EGraph,
+    costs: HashMap<Id, Cost>,
+    chosen: HashMap<Id, Cad>,
+    order: HashMap<(Id, Id), Ordering>,
+}
+
+impl<'a> TieBreak<'a> {
+    fn choose(&mut self, id: Id) -> Cad {
+        let id = self.egraph.find(id);
+        if let Some(node) = self.chosen.get(&id) {
+            return node.clone();
+        }
+        let best = self.costs[&id];
+        let candidates: Vec<Cad> = self.egraph[id]
+            .nodes
+            .iter()
+            .filter(
+                |n| matches!(node_cost(self.egraph, &self.costs, n), Some(c) if is_tie(c, best)),
+            )
+            .cloned()
+            .collect();
+        let mut candidates = candidates.into_iter();
+        let mut node = candidates.next().expect("e-class without a costed e-node");
+        for candidate in candidates {
+            if self.cmp_nodes(&candidate, &node) == Ordering::Less {
+                node = candidate;
+            }
+        }
+        self.chosen.insert(id, node.clone());
+        node
+    }
+
+    fn cmp_nodes(&mut self, a: &Cad, b: &Cad) -> Ordering {
+        let op = |n: &Cad| n.clone().map_children(|_| Id::from(0));
+        let ord = op(a).cmp(&op(b));
+        if ord != Ordering::Equal {
+            return ord;
+        }
+        for (&x, &y) in a.children().iter().zip(b.children()) {
+            let ord = self.cmp_classes(x, y);
+            if ord != Ordering::Equal {
+                return ord;
+            }
+        }
+        Ordering::Equal
+    }
+
+    fn cmp_classes(&mut self, x: Id, y: Id) -> Ordering {
+        let (x, y) = (self.egraph.find(x), self.egraph.find(y));
+        if x == y {
+            return Ordering::Equal;
+        }
+        if let Some(&ord) = self.order.get(&(x, y)) {
+            return ord;
+        }
+        let (a, b) = (self.choose(x), self.choose(y));
+        let ord = self.cmp_nodes(&a, &b);
+        self.order.insert((x, y), ord);
+        self.order.insert((y, x), ord.reverse());
+        ord
+    }
+}
+
+/// Extract the cheapest term of `root`. Ties between equally cheap terms are broken on the terms
+/// themselves, so the result does not depend on the order e-nodes were added in.
+pub fn extract_best(egraph: &EGraph, root: Id) -> (Cost, RecExpr<Cad>) {
+    let mut tie_break = TieBreak {
+        egraph,
+        costs: class_costs(egraph),
+        chosen: HashMap::new(),
+        order: HashMap::new(),
+    };
+    let cost = tie_break.costs[&egraph.find(root)];
+    let root = tie_break.choose(root);
+    let expr = root.build_recexpr(|id| tie_break.choose(id));
+    (cost, expr)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cad::{MetaAnalysis, Rewrite};
+    use crate::rules::cad_rules;
+    use egg::Runner;
+
+    fn extract_after_adding(exprs: &[&str]) -> String {
+        let mut egraph = EGraph::new(MetaAnalysis);
+        let ids: Vec<Id> = exprs
+            .iter()
+            .map(|e| egraph.add_expr(&e.parse().unwrap()))
+            .collect();
+        for &id in &ids[1..] {
+            egraph.union(ids[0], id);
+        }
+        egraph.rebuild();
+        extract_best(&egraph, ids[0]).1.to_string()
+    }
+
+    #[test]
+    fn equal_cost_extraction_is_order_independent() {
+        let a = "(Binop Union (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false))";
+        let b = "(Binop Union (Cube (Vec3 2 2 2) false) (Cube (Vec3 1 1 1) false))";
+        let ab = extract_after_adding(&[a, b]);
+        let ba = extract_after_adding(&[b, a]);
+        assert_eq!(ab, ba);
+        assert_eq!(ab, a);
+    }
+
+    fn optimize(input: &str, rules: &[Rewrite]) -> String {
+        let expr: RecExpr<Cad> = input.parse().unwrap();
+        let runner: Runner<Cad, MetaAnalysis> = Runner::new(MetaAnalysis)
+            .with_iter_limit(5)
+            .with_expr(&expr)
+            .run(rules);
+        extract_best(&runner.egraph, runner.roots[0]).1.to_string()
+    }
+
+    #[test]
+    fn optimizing_twice_is_byte_identical() {
+        let input = "(Binop Union
+            (Affine Trans (Vec3 1 2 3) (Cube (Vec3 2 2 2) false))
+            (Binop Union
+              (Affine Trans (Vec3 1 2 3) (Cube (Vec3 1 1 1) false))
+              (Cube (Vec3 1 1 1) false)))";
+        let rules = cad_rules();
+        assert_eq!(optimize(input, &rules), optimize(input, &rules));
+    }
+
+    #[test]
+    fn extraction_is_linear_in_shared_size() {
+        // every level reuses the one below twice, so the term is exponentially larger as a tree
+        let mut egraph = EGraph::new(MetaAnalysis);
+        let diff = egraph.add(Cad::Diff);
+        let size = egraph.add(Cad::Num(1.0.into()));
+        let vec = egraph.add(Cad::Vec3([size, size, size]));
+        let center = egraph.add(Cad::Bool(false));
+        let mut id = egraph.add(Cad::Cube([vec, center]));
+        for _ in 0..40 {
+            id = egraph.add(Cad::Binop([diff, id, id]));
+        }
+        egraph.rebuild();
+        let (_, expr) = extract_best(&egraph, id);
+        assert_eq!(expr.as_ref().len(), 5 + 40);
+    }
+}
@@ -1 +1,2 @@
 pub mod scad;
+pub mod stl;
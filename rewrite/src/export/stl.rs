@@ -0,0 +1,770 @@
+/// Stl
+///
+/// Primitives are tessellated with the OpenSCAD fragment rules, and boolean operations are
+/// performed on the resulting polygon meshes with BSP trees, in the spirit of csg.js. The result
+/// is only as exact as the tessellation.
+use std::f64::consts::PI;
+
+use egg::{Id, RecExpr};
+
+use crate::cad::Cad;
+use crate::cad_struct::{get_num, get_vec3_nums};
+
+use crate::eval::eval;
+
+// tolerance used to decide on which side of a plane a point is
+const PLANE_EPSILON: f64 = 1e-5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Point(f64, f64, f64);
+
+impl Point {
+    fn add(self, o: Point) -> Point {
+        Point(self.0 + o.0, self.1 + o.1, self.2 + o.2)
+    }
+
+    fn sub(self, o: Point) -> Point {
+        Point(self.0 - o.0, self.1 - o.1, self.2 - o.2)
+    }
+
+    fn times(self, k: f64) -> Point {
+        Point(self.0 * k, self.1 * k, self.2 * k)
+    }
+
+    fn dot(self, o: Point) -> f64 {
+        self.0 * o.0 + self.1 * o.1 + self.2 * o.2
+    }
+
+    fn cross(self, o: Point) -> Point {
+        Point(
+            self.1 * o.2 - self.2 * o.1,
+            self.2 * o.0 - self.0 * o.2,
+            self.0 * o.1 - self.1 * o.0,
+        )
+    }
+
+    fn length(self) -> f64 {
+        self.dot(self).sqrt()
+    }
+
+    fn lerp(self, o: Point, t: f64) -> Point {
+        self.add(o.sub(self).times(t))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Plane {
+    normal: Point,
+    w: f64,
+}
+
+impl Plane {
+    fn flip(&mut self) {
+        self.normal = self.normal.times(-1.0);
+        self.w = -self.w;
+    }
+}
+
+// A convex planar polygon, vertices in counter-clockwise order seen from outside
+#[derive(Debug, Clone)]
+struct Polygon {
+    vertices: Vec<Point>,
+    plane: Plane,
+}
+
+impl Polygon {
+    // None if the polygon is degenerate
+    fn new(mut vertices: Vec<Point>) -> Option<Polygon> {
+        let same = |a: &Point, b: &Point| a.sub(*b).length() < PLANE_EPSILON;
+        vertices.dedup_by(|a, b| same(a, b));
+        while vertices.len() > 1 && same(&vertices[0], &vertices[vertices.len() - 1]) {
+            vertices.pop();
+        }
+        if vertices.len() < 3 {
+            return None;
+        }
+        // Newell's method, robust to collinear leading vertices
+        let mut normal = Point(0.0, 0.0, 0.0);
+        for (i, a) in vertices.iter().enumerate() {
+            let b = vertices[(i + 1) % vertices.len()];
+            normal = normal.add(a.cross(b));
+        }
+        let len = normal.length();
+        if len < PLANE_EPSILON {
+            return None;
+        }
+        let normal = normal.times(1.0 / len);
+        let w = normal.dot(vertices[0]);
+        Some(Polygon {
+            vertices,
+            plane: Plane { normal, w },
+        })
+    }
+
+    fn flip(&mut self) {
+        self.vertices.reverse();
+        self.plane.flip();
+    }
+
+    fn map(&self, f: impl Fn(Point) -> Point) -> Option<Polygon> {
+        Polygon::new(self.vertices.iter().map(|&v| f(v)).collect())
+    }
+}
+
+type Mesh = Vec<Polygon>;
+
+const COPLANAR: u8 = 0;
+const FRONT: u8 = 1;
+const BACK: u8 = 2;
+const SPANNING: u8 = 3;
+
+// Split `polygon` by `plane`, coplanar polygons go to either `front` or `back` depending on
+// their orientation unless `coplanar` is given.
+fn split_polygon(
+    plane: &Plane,
+    polygon: &Polygon,
+    mut coplanar: Option<&mut Mesh>,
+    front: &mut Mesh,
+    back: &mut Mesh,
+) {
+    let types: Vec<u8> = polygon
+        .vertices
+        .iter()
+        .map(|v| {
+            let t = plane.normal.dot(*v) - plane.w;
+            if t < -PLANE_EPSILON {
+                BACK
+            } else if t > PLANE_EPSILON {
+                FRONT
+            } else {
+                COPLANAR
+            }
+        })
+        .collect();
+
+    match types.iter().fold(COPLANAR, |acc, t| acc | t) {
+        COPLANAR => match coplanar.as_mut() {
+            Some(coplanar) => coplanar.push(polygon.clone()),
+            None if plane.normal.dot(polygon.plane.normal) > 0.0 => front.push(polygon.clone()),
+            None => back.push(polygon.clone()),
+        },
+        FRONT => front.push(polygon.clone()),
+        BACK => back.push(polygon.clone()),
+        _ => {
+            let n = polygon.vertices.len();
+            let mut f = vec![];
+            let mut b = vec![];
+            for i in 0..n {
+                let j = (i + 1) % n;
+                let (ti, tj) = (types[i], types[j]);
+                let (vi, vj) = (polygon.vertices[i], polygon.vertices[j]);
+                if ti != BACK {
+                    f.push(vi);
+                }
+                if ti != FRONT {
+                    b.push(vi);
+                }
+                if ti | tj == SPANNING {
+                    let t = (plane.w - plane.normal.dot(vi)) / plane.normal.dot(vj.sub(vi));
+                    let v = vi.lerp(vj, t);
+                    f.push(v);
+                    b.push(v);
+                }
+            }
+            front.extend(Polygon::new(f));
+            back.extend(Polygon::new(b));
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Node {
+    plane: Option<Plane>,
+    front: Option<Box<Node>>,
+    back: Option<Box<Node>>,
+    polygons: Mesh,
+}
+
+impl Node {
+    fn new(polygons: Mesh) -> Node {
+        let mut node = Node::default();
+        node.build(polygons);
+        node
+    }
+
+    // Turn solid space into empty space and vice versa
+    fn invert(&mut self) {
+        for p in &mut self.polygons {
+            p.flip();
+        }
+        if let Some(plane) = &mut self.plane {
+            plane.flip();
+        }
+        if let Some(front) = &mut self.front {
+            front.invert();
+        }
+        if let Some(back) = &mut self.back {
+            back.invert();
+        }
+        std::mem::swap(&mut self.front, &mut self.back);
+    }
+
+    // Remove the parts of `polygons` that are inside this tree
+    fn clip_polygons(&self, polygons: &[Polygon]) -> Mesh {
+        let plane = match &self.plane {
+            Some(plane) => plane,
+            None => return polygons.to_vec(),
+        };
+        let mut front = vec![];
+        let mut back = vec![];
+        for p in polygons {
+            split_polygon(plane, p, None, &mut front, &mut back);
+        }
+        let mut front = match &self.front {
+            Some(node) => node.clip_polygons(&front),
+            None => front,
+        };
+        let back = match &self.back {
+            Some(node) => node.clip_polygons(&back),
+            None => vec![],
+        };
+        front.extend(back);
+        front
+    }
+
+    // Remove the parts of this tree that are inside `bsp`
+    fn clip_to(&mut self, bsp: &Node) {
+        self.polygons = bsp.clip_polygons(&self.polygons);
+        if let Some(front) = &mut self.front {
+            front.clip_to(bsp);
+        }
+        if let Some(back) = &mut self.back {
+            back.clip_to(bsp);
+        }
+    }
+
+    fn all_polygons(&self) -> Mesh {
+        let mut polygons = self.polygons.clone();
+        if let Some(front) = &self.front {
+            polygons.extend(front.all_polygons());
+        }
+        if let Some(back) = &self.back {
+            polygons.extend(back.all_polygons());
+        }
+        polygons
+    }
+
+    fn build(&mut self, polygons: Mesh) {
+        if polygons.is_empty() {
+            return;
+        }
+        let plane = *self.plane.get_or_insert(polygons[0].plane);
+        let mut coplanar = vec![];
+        let mut front = vec![];
+        let mut back = vec![];
+        for p in &polygons {
+            split_polygon(&plane, p, Some(&mut coplanar), &mut front, &mut back);
+        }
+        self.polygons.extend(coplanar);
+        if !front.is_empty() {
+            self.front.get_or_insert_with(Default::default).build(front);
+        }
+        if !back.is_empty() {
+            self.back.get_or_insert_with(Default::default).build(back);
+        }
+    }
+}
+
+fn union(a: Mesh, b: Mesh) -> Mesh {
+    if a.is_empty() || b.is_empty() {
+        return if a.is_empty() { b } else { a };
+    }
+    let mut a = Node::new(a);
+    let mut b = Node::new(b);
+    a.clip_to(&b);
+    b.clip_to(&a);
+    b.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.build(b.all_polygons());
+    a.all_polygons()
+}
+
+fn diff(a: Mesh, b: Mesh) -> Mesh {
+    if a.is_empty() || b.is_empty() {
+        return a;
+    }
+    let mut a = Node::new(a);
+    let mut b = Node::new(b);
+    a.invert();
+    a.clip_to(&b);
+    b.clip_to(&a);
+    b.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.build(b.all_polygons());
+    a.invert();
+    a.all_polygons()
+}
+
+fn inter(a: Mesh, b: Mesh) -> Mesh {
+    if a.is_empty() || b.is_empty() {
+        return vec![];
+    }
+    let mut a = Node::new(a);
+    let mut b = Node::new(b);
+    a.invert();
+    b.clip_to(&a);
+    b.invert();
+    a.clip_to(&b);
+    b.clip_to(&a);
+    a.build(b.all_polygons());
+    a.invert();
+    a.all_polygons()
+}
+
+// Number of fragments of a circle of radius `r`, following OpenSCAD
+fn fragments(r: f64, (fn_, fa, fs): (f64, f64, f64)) -> usize {
+    if r < 1e-6 {
+        3
+    } else if fn_ > 0.0 {
+        (fn_ as usize).max(3)
+    } else {
+        let fa = fa.max(0.01);
+        let fs = fs.max(0.01);
+        (360.0 / fa).min(r * 2.0 * PI / fs).max(5.0).ceil() as usize
+    }
+}
+
+// Orient every face of a convex solid around `center` outwards
+fn convex(faces: Vec<Vec<Point>>, center: Point) -> Mesh {
+    faces
+        .into_iter()
+        .filter_map(Polygon::new)
+        .map(|mut p| {
+            let centroid = p
+                .vertices
+                .iter()
+                .fold(Point(0.0, 0.0, 0.0), |acc, &v| acc.add(v))
+                .times(1.0 / p.vertices.len() as f64);
+            if p.plane.normal.dot(centroid.sub(center)) < 0.0 {
+                p.flip();
+            }
+            p
+        })
+        .collect()
+}
+
+fn cube((x, y, z): (f64, f64, f64), center: bool) -> Mesh {
+    let o = if center {
+        Point(-x / 2.0, -y / 2.0, -z / 2.0)
+    } else {
+        Point(0.0, 0.0, 0.0)
+    };
+    let corner = |i: usize, j: usize, k: usize| {
+        o.add(Point(
+            if i == 1 { x } else { 0.0 },
+            if j == 1 { y } else { 0.0 },
+            if k == 1 { z } else { 0.0 },
+        ))
+    };
+    let faces = vec![
+        vec![
+            corner(0, 0, 0),
+            corner(0, 1, 0),
+            corner(0, 1, 1),
+            corner(0, 0, 1),
+        ],
+        vec![
+            corner(1, 0, 0),
+            corner(1, 1, 0),
+            corner(1, 1, 1),
+            corner(1, 0, 1),
+        ],
+        vec![
+            corner(0, 0, 0),
+            corner(1, 0, 0),
+            corner(1, 0, 1),
+            corner(0, 0, 1),
+        ],
+        vec![
+            corner(0, 1, 0),
+            corner(1, 1, 0),
+            corner(1, 1, 1),
+            corner(0, 1, 1),
+        ],
+        vec![
+            corner(0, 0, 0),
+            corner(1, 0, 0),
+            corner(1, 1, 0),
+            corner(0, 1, 0),
+        ],
+        vec![
+            corner(0, 0, 1),
+            corner(1, 0, 1),
+            corner(1, 1, 1),
+            corner(0, 1, 1),
+        ],
+    ];
+    convex(faces, o.add(Point(x / 2.0, y / 2.0, z / 2.0)))
+}
+
+fn sphere(r: f64, params: (f64, f64, f64)) -> Mesh {
+    let slices = fragments(r, params);
+    #[allow(clippy::manual_div_ceil)] // usize::div_ceil needs Rust 1.73
+    let stacks = (slices + 1) / 2;
+    let vertex = |i: usize, j: usize| {
+        let theta = 2.0 * PI * i as f64 / slices as f64;
+        let phi = PI * j as f64 / stacks as f64;
+        Point(
+            r * theta.cos() * phi.sin(),
+            r * theta.sin() * phi.sin(),
+            r * phi.cos(),
+        )
+    };
+    let mut faces = vec![];
+    for i in 0..slices {
+        for j in 0..stacks {
+            faces.push(vec![
+                vertex(i, j),
+                vertex(i + 1, j),
+                vertex(i + 1, j + 1),
+                vertex(i, j + 1),
+            ]);
+        }
+    }
+    convex(faces, Point(0.0, 0.0, 0.0))
+}
+
+fn cylinder((h, r1, r2): (f64, f64, f64), params: (f64, f64, f64), center: bool) -> Mesh {
+    let n = fragments(r1.max(r2), params);
+    let z0 = if center { -h / 2.0 } else { 0.0 };
+    let ring = |r: f64, z: f64| -> Vec<Point> {
+        (0..n)
+            .map(|i| {
+                let theta = 2.0 * PI * i as f64 / n as f64;
+                Point(r * theta.cos(), r * theta.sin(), z)
+            })
+            .collect()
+    };
+    let bottom = ring(r1, z0);
+    let top = ring(r2, z0 + h);
+    let mut faces = vec![bottom.clone(), top.clone()];
+    for i in 0..n {
+        let j = (i + 1) % n;
+        faces.push(vec![bottom[i], bottom[j], top[j], top[i]]);
+    }
+    convex(faces, Point(0.0, 0.0, z0 + h / 2.0))
+}
+
+// Convex hull of `points` by incremental construction, flat or degenerate hulls are empty
+fn hull(mut points: Vec<Point>) -> Mesh {
+    let same = |a: Point, b: Point| a.sub(b).length() < PLANE_EPSILON;
+    let mut unique: Vec<Point> = vec![];
+    points.retain(|&p| {
+        let new = !unique.iter().any(|&q| same(p, q));
+        if new {
+            unique.push(p);
+        }
+        new
+    });
+    if points.len() < 4 {
+        return vec![];
+    }
+
+    let normal = |[a, b, c]: [usize; 3]| {
+        let n = points[b].sub(points[a]).cross(points[c].sub(points[a]));
+        n.times(1.0 / n.length())
+    };
+    let height = |face: [usize; 3], p: Point| normal(face).dot(p.sub(points[face[0]]));
+
+    // initial tetrahedron from the most spread out points
+    let farthest = |score: &dyn Fn(Point) -> f64| {
+        (0..points.len())
+            .max_by(|&i, &j| score(points[i]).partial_cmp(&score(points[j])).unwrap())
+            .unwrap()
+    };
+    let i0 = 0;
+    let i1 = farthest(&|p| p.sub(points[i0]).length());
+    let line = points[i1].sub(points[i0]);
+    let i2 = farthest(&|p| line.cross(p.sub(points[i0])).length());
+    if line.cross(points[i2].sub(points[i0])).length() < PLANE_EPSILON {
+        return vec![];
+    }
+    let i3 = farthest(&|p| height([i0, i1, i2], p).abs());
+    if height([i0, i1, i2], points[i3]).abs() < PLANE_EPSILON {
+        return vec![];
+    }
+    let center = [i0, i1, i2, i3]
+        .iter()
+        .fold(Point(0.0, 0.0, 0.0), |acc, &i| acc.add(points[i]))
+        .times(0.25);
+    let mut faces: Vec<[usize; 3]> = [[i0, i1, i2], [i0, i1, i3], [i0, i2, i3], [i1, i2, i3]]
+        .iter()
+        .map(|&[a, b, c]| {
+            if height([a, b, c], center) > 0.0 {
+                [a, c, b]
+            } else {
+                [a, b, c]
+            }
+        })
+        .collect();
+
+    for (p, &point) in points.iter().enumerate() {
+        let (visible, hidden): (Vec<_>, Vec<_>) = faces
+            .into_iter()
+            .partition(|&face| height(face, point) > PLANE_EPSILON);
+        faces = hidden;
+        // the horizon consists of the edges of visible faces whose neighbour is not visible
+        for &[a, b, c] in &visible {
+            for (u, v) in [(a, b), (b, c), (c, a)] {
+                let shared = visible
+                    .iter()
+                    .any(|f| (0..3).any(|k| f[k] == v && f[(k + 1) % 3] == u));
+                if !shared {
+                    faces.push([u, v, p]);
+                }
+            }
+        }
+    }
+
+    let faces = faces
+        .into_iter()
+        .map(|face| face.iter().map(|&i| points[i]).collect())
+        .collect();
+    convex(faces, center)
+}
+
+fn rotate((a, b, c): (f64, f64, f64), p: Point) -> Point {
+    // OpenSCAD rotates around x, then y, then z
+    let rot = |deg: f64| {
+        let rad = deg * PI / 180.0;
+        (rad.cos(), rad.sin())
+    };
+    let (ca, sa) = rot(a);
+    let (cb, sb) = rot(b);
+    let (cc, sc) = rot(c);
+    let p = Point(p.0, ca * p.1 - sa * p.2, sa * p.1 + ca * p.2);
+    let p = Point(cb * p.0 + sb * p.2, p.1, -sb * p.0 + cb * p.2);
+    Point(cc * p.0 - sc * p.1, sc * p.0 + cc * p.1, p.2)
+}
+
+fn get_bool(expr: &RecExpr<Cad>, p: Id) -> bool {
+    match expr[p] {
+        Cad::Bool(b) => b,
+        _ => panic!("Not a bool"),
+    }
+}
+
+fn mesh(expr: &RecExpr<Cad>, p: Id) -> Mesh {
+    match &expr[p] {
+        Cad::Empty => vec![],
+        Cad::Cube(args) => cube(get_vec3_nums(expr, args[0]), get_bool(expr, args[1])),
+        Cad::Sphere(args) => sphere(get_num(expr, args[0]), get_vec3_nums(expr, args[1])),
+        Cad::Cylinder(args) => cylinder(
+            get_vec3_nums(expr, args[0]),
+            get_vec3_nums(expr, args[1]),
+            get_bool(expr, args[2]),
+        ),
+        Cad::Affine(args) => {
+            let (x, y, z) = get_vec3_nums(expr, args[1]);
+            let cad = mesh(expr, args[2]);
+            let f: Box<dyn Fn(Point) -> Point> = match expr[args[0]] {
+                Cad::Trans => Box::new(move |p| p.add(Point(x, y, z))),
+                Cad::Scale => Box::new(move |p| Point(p.0 * x, p.1 * y, p.2 * z)),
+                Cad::Rotate => Box::new(move |p| rotate((x, y, z), p)),
+                ref aff => panic!("expected affine kind, got {:?}", aff),
+            };
+            // Polygon::new recomputes the normal from the winding, so a mirroring scale needs
+            // the winding reversed to keep faces pointing outwards
+            let mirror = matches!(expr[args[0]], Cad::Scale) && x * y * z < 0.0;
+            cad.iter()
+                .filter_map(|poly| poly.map(&f))
+                .map(|mut poly| {
+                    if mirror {
+                        poly.flip();
+                    }
+                    poly
+                })
+                .collect()
+        }
+        Cad::Fold(args) => {
+            let cads = match &expr[args[1]] {
+                Cad::List(list) => list.iter().map(|&c| mesh(expr, c)),
+                cad => panic!("expected list, got {:?}", cad),
+            };
+            let op = match expr[args[0]] {
+                Cad::Union => union,
+                Cad::Diff => diff,
+                Cad::Inter => inter,
+                ref bop => panic!("unexpected binop: {:?}", bop),
+            };
+            cads.reduce(op).unwrap_or_default()
+        }
+        Cad::Hull(args) => {
+            let points = match &expr[args[0]] {
+                Cad::List(list) => list
+                    .iter()
+                    .flat_map(|&c| mesh(expr, c))
+                    .flat_map(|poly| poly.vertices)
+                    .collect(),
+                cad => panic!("expected list, got {:?}", cad),
+            };
+            hull(points)
+        }
+        cad => panic!("unexpected node in normal form: {:?}", cad),
+    }
+}
+
+fn write_point(out: &mut Vec<u8>, p: Point) {
+    for c in [p.0, p.1, p.2] {
+        out.extend((c as f32).to_le_bytes());
+    }
+}
+
+/// Export the CAD expression as a binary STL triangle mesh.
+pub fn export_stl(expr: &RecExpr<Cad>) -> Vec<u8> {
+    let mut normalform = RecExpr::from(vec![]);
+    let p = eval(
+        None,
+        expr,
+        (expr.as_ref().len() - 1).into(),
+        &mut normalform,
+    );
+    let mesh = mesh(&normalform, p);
+
+    // fan triangulation of the convex polygons
+    let triangles: Vec<(Point, [Point; 3])> = mesh
+        .iter()
+        .flat_map(|poly| {
+            let v = &poly.vertices;
+            (1..v.len() - 1).map(move |i| (poly.plane.normal, [v[0], v[i], v[i + 1]]))
+        })
+        .collect();
+
+    let mut out = vec![0u8; 80];
+    out.extend((triangles.len() as u32).to_le_bytes());
+    for (normal, vertices) in triangles {
+        write_point(&mut out, normal);
+        for v in vertices {
+            write_point(&mut out, v);
+        }
+        out.extend(0u16.to_le_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryInto;
+
+    fn read_f32(bytes: &[u8], at: usize) -> f64 {
+        f32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as f64
+    }
+
+    fn triangle_count(stl: &[u8]) -> usize {
+        u32::from_le_bytes(stl[80..84].try_into().unwrap()) as usize
+    }
+
+    // signed volume enclosed by the triangles, via the divergence theorem
+    fn stl_volume(stl: &[u8]) -> f64 {
+        let n = triangle_count(stl);
+        assert_eq!(stl.len(), 84 + 50 * n);
+        (0..n)
+            .map(|t| {
+                let base = 84 + 50 * t + 12;
+                let v = |i: usize| {
+                    let at = base + 12 * i;
+                    Point(
+                        read_f32(stl, at),
+                        read_f32(stl, at + 4),
+                        read_f32(stl, at + 8),
+                    )
+                };
+                v(0).dot(v(1).cross(v(2))) / 6.0
+            })
+            .sum()
+    }
+
+    fn stl_of(s: &str) -> Vec<u8> {
+        export_stl(&s.parse().unwrap())
+    }
+
+    #[test]
+    fn single_cube() {
+        let stl = stl_of("(Cube (Vec3 1 2 3) false)");
+        assert_eq!(triangle_count(&stl), 12);
+        assert!((stl_volume(&stl) - 6.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn affine_keeps_orientation() {
+        let stl = stl_of(
+            "(Affine Trans (Vec3 5 5 5)
+              (Affine Rotate (Vec3 30 45 60)
+                (Affine Scale (Vec3 -1 2 2) (Cube (Vec3 1 1 1) true))))",
+        );
+        assert_eq!(triangle_count(&stl), 12);
+        assert!((stl_volume(&stl) - 4.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn primitives_are_closed() {
+        let sphere = stl_of("(Sphere 1 (Vec3 32 0 2))");
+        let exact = 4.0 / 3.0 * PI;
+        assert!(stl_volume(&sphere) > 0.9 * exact && stl_volume(&sphere) < exact);
+        let cone = stl_of("(Cylinder (Vec3 3 1 0) (Vec3 32 0 2) true)");
+        let exact = PI;
+        assert!(stl_volume(&cone) > 0.9 * exact && stl_volume(&cone) < exact);
+    }
+
+    #[test]
+    fn boolean_ops() {
+        let union = stl_of(
+            "(Binop Union
+              (Cube (Vec3 2 2 2) false)
+              (Affine Trans (Vec3 1 1 1) (Cube (Vec3 2 2 2) false)))",
+        );
+        assert!((stl_volume(&union) - 15.0).abs() < 1e-4);
+        let diff = stl_of(
+            "(Binop Diff
+              (Cube (Vec3 2 2 2) false)
+              (Affine Trans (Vec3 1 1 1) (Cube (Vec3 2 2 2) false)))",
+        );
+        assert!((stl_volume(&diff) - 7.0).abs() < 1e-4);
+        let inter = stl_of(
+            "(Binop Inter
+              (Cube (Vec3 2 2 2) false)
+              (Affine Trans (Vec3 1 1 1) (Cube (Vec3 2 2 2) false)))",
+        );
+        assert!((stl_volume(&inter) - 1.0).abs() < 1e-4);
+        let disjoint = stl_of(
+            "(Binop Inter
+              (Cube (Vec3 1 1 1) false)
+              (Affine Trans (Vec3 5 5 5) (Cube (Vec3 1 1 1) false)))",
+        );
+        assert_eq!(triangle_count(&disjoint), 0);
+    }
+
+    #[test]
+    fn hull() {
+        let boxes = stl_of(
+            "(Hull (List
+              (Cube (Vec3 1 1 1) false)
+              (Affine Trans (Vec3 2 0 0) (Cube (Vec3 1 1 1) false))))",
+        );
+        assert!((stl_volume(&boxes) - 3.0).abs() < 1e-4);
+        // a hexagonal prism, the 2x2 square minus two corner triangles
+        let diagonal = stl_of(
+            "(Hull (List
+              (Cube (Vec3 1 1 1) false)
+              (Affine Trans (Vec3 1 1 0) (Cube (Vec3 1 1 1) false))))",
+        );
+        assert!((stl_volume(&diagonal) - 3.0).abs() < 1e-4);
+        let sphere = stl_of("(Hull (List (Sphere 1 (Vec3 32 0 2))))");
+        assert!(
+            (stl_volume(&sphere) - stl_volume(&stl_of("(Sphere 1 (Vec3 32 0 2))"))).abs() < 1e-4
+        );
+    }
+}
@@ -52,5 +52,8 @@ pub mod rules;
 pub mod eval;
 mod solve;
 
+// Optimization report
+pub mod report;
+
 // Export
 pub mod export;
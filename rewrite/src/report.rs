@@ -0,0 +1,122 @@
+use egg::{EGraph, IterationData, RecExpr, Runner, StopReason};
+use indexmap::IndexSet;
+use serde::Serialize;
+
+use crate::cad::{Cad, MetaAnalysis};
+use crate::cost::extract_best;
+
+/// How much an optimization run shrank the program.
+#[derive(Debug, Serialize)]
+pub struct CadOptimizeReport {
+    pub input_nodes: usize,
+    pub output_nodes: usize,
+    /// names of the rules that fired at least once, in the order they first fired
+    pub rules_applied: Vec<String>,
+    /// why each of the runners stopped, in the order they ran
+    pub stop_reasons: Vec<StopReason>,
+}
+
+impl CadOptimizeReport {
+    /// `input` should be the program as given, before pruning, so that the nodes removed by
+    /// `prune::remove_empty` are accounted for as well. `runners` are all the runners of the
+    /// pipeline, in the order they ran.
+    pub fn new<D: IterationData<Cad, MetaAnalysis>>(
+        input: &RecExpr<Cad>,
+        output: &RecExpr<Cad>,
+        runners: &[&Runner<Cad, MetaAnalysis, D>],
+    ) -> CadOptimizeReport {
+        let rules_applied: IndexSet<String> = runners
+            .iter()
+            .flat_map(|runner| runner.iterations.iter())
+            .flat_map(|iter| iter.applied.iter())
+            .filter(|(_, &n)| n > 0)
+            .map(|(name, _)| name.to_string())
+            .collect();
+        CadOptimizeReport {
+            input_nodes: shared_size(input),
+            output_nodes: output.as_ref().len(),
+            rules_applied: rules_applied.into_iter().collect(),
+            stop_reasons: runners
+                .iter()
+                .map(|runner| runner.stop_reason.clone().expect("runner has not been run"))
+                .collect(),
+        }
+    }
+
+    pub fn node_reduction(&self) -> isize {
+        self.input_nodes as isize - self.output_nodes as isize
+    }
+}
+
+// Size of `expr` with shared subterms counted once, the way an extracted program is counted
+fn shared_size(expr: &RecExpr<Cad>) -> usize {
+    let mut egraph = EGraph::new(MetaAnalysis);
+    let root = egraph.add_expr(expr);
+    egraph.rebuild();
+    extract_best(&egraph, root).1.as_ref().len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::cad_rules;
+    use egg::Rewrite;
+
+    fn report(input: &str, rules: &[Rewrite<Cad, MetaAnalysis>]) -> CadOptimizeReport {
+        let input: RecExpr<Cad> = input.parse().unwrap();
+        let runner: Runner<Cad, MetaAnalysis> = Runner::new(MetaAnalysis)
+            .with_iter_limit(5)
+            .with_expr(&input)
+            .run(rules);
+        let (_, output) = extract_best(&runner.egraph, runner.roots[0]);
+        CadOptimizeReport::new(&input, &output, &[&runner])
+    }
+
+    #[test]
+    fn union_same_report() {
+        let report = report(
+            "(Binop Union (Cube (Vec3 1 1 1) false) (Cube (Vec3 1 1 1) false))",
+            &cad_rules(),
+        );
+        // the shared cube is counted once, so only the Binop and Union nodes go away
+        assert_eq!(report.input_nodes, 6);
+        assert_eq!(report.output_nodes, 4);
+        assert_eq!(report.node_reduction(), 2);
+        assert!(report.rules_applied.iter().any(|r| r == "union_same"));
+    }
+
+    #[test]
+    fn no_op_report() {
+        let report = report(
+            "(Binop Diff
+              (Cube (Vec3 1 1 1) false)
+              (Affine Trans (Vec3 1 1 1) (Cube (Vec3 1 1 1) false)))",
+            &[],
+        );
+        assert_eq!(report.input_nodes, 8);
+        assert_eq!(report.output_nodes, 8);
+        assert_eq!(report.node_reduction(), 0);
+        assert!(report.rules_applied.is_empty());
+        assert_eq!(report.stop_reasons.len(), 1);
+    }
+
+    #[test]
+    fn merges_runners() {
+        let input: RecExpr<Cad> =
+            "(Binop Union (Cube (Vec3 1 1 1) false) (Cube (Vec3 1 1 1) false))"
+                .parse()
+                .unwrap();
+        let noop: Runner<Cad, MetaAnalysis> = Runner::new(MetaAnalysis)
+            .with_iter_limit(5)
+            .with_expr(&input)
+            .run(&[]);
+        let runner: Runner<Cad, MetaAnalysis> = Runner::new(MetaAnalysis)
+            .with_iter_limit(5)
+            .with_expr(&input)
+            .run(&cad_rules());
+        let (_, output) = extract_best(&runner.egraph, runner.roots[0]);
+        let report = CadOptimizeReport::new(&input, &output, &[&noop, &runner]);
+        assert_eq!(report.stop_reasons.len(), 2);
+        assert!(report.rules_applied.iter().any(|r| r == "union_same"));
+    }
+}
@@ -28,6 +28,36 @@ fn is_eq(v1: &'static str, v2: &'static str) -> ConditionEqual<Cad> {
     ConditionEqual::new(p1, p2)
 }
 
+fn is_one_of(var: &'static str, cads: &[Cad]) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let var = var.parse().unwrap();
+    let cads = cads.to_vec();
+    move |egraph, _, subst| egraph[subst[var]].nodes.iter().any(|n| cads.contains(n))
+}
+
+// An affine can be pulled out of an Inter only if it maps distinct points to distinct points,
+// which a scale with a zero component does not.
+fn distributes_over_inter(
+    bop: &'static str,
+    aff: &'static str,
+    param: &'static str,
+) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
+    let bop: Var = bop.parse().unwrap();
+    let aff: Var = aff.parse().unwrap();
+    let param: Var = param.parse().unwrap();
+    let zero = Cad::Num(num(0.0));
+    move |egraph, _, subst| {
+        let is_inter = egraph[subst[bop]].nodes.contains(&Cad::Inter);
+        let is_scale = egraph[subst[aff]].nodes.contains(&Cad::Scale);
+        if !is_inter || !is_scale {
+            return true;
+        }
+        egraph[subst[param]].nodes.iter().any(|n| match n {
+            Cad::Vec3(args) => args.iter().all(|a| !egraph[*a].nodes.contains(&zero)),
+            _ => false,
+        })
+    }
+}
+
 fn is_pos(vars: &[&'static str]) -> impl Fn(&mut EGraph, Id, &Subst) -> bool {
     let vars: Vec<Var> = vars.iter().map(|v| v.parse().unwrap()).collect();
     move |egraph, _, subst| {
@@ -103,12 +133,32 @@ pub fn cad_rules() -> Vec<Rewrite> {
         rw!("fold_op"; "(Fold ?bop (Affine ?aff ?param ?cad))"=> "(Affine ?aff ?param (Fold ?bop ?cad))"),
 
         rw!("union_trans"; "(Binop Union (Affine Trans (Vec3 ?x ?y ?z) ?a) (Affine Trans (Vec3 ?x ?y ?z) ?b))"=> "(Affine Trans (Vec3 ?x ?y ?z) (Binop Union ?a ?b))"),
+        rw!("inter_trans"; "(Binop Inter (Affine Trans ?v ?a) (Affine Trans ?v ?b))"=> "(Affine Trans ?v (Binop Inter ?a ?b))"),
+        rw!("affine_binop"; "(Binop ?bop (Affine ?aff ?param ?a) (Affine ?aff ?param ?b))"=> "(Affine ?aff ?param (Binop ?bop ?a ?b))"
+            if is_one_of("?bop", &[Cad::Union, Cad::Inter])
+            if distributes_over_inter("?bop", "?aff", "?param")),
 
         // Related to Boolean Operators
         rw!("union_same"; "(Binop Union ?a ?a)"=> "?a"),
         rw!("inter_same"; "(Binop Inter ?a ?a)"=> "?a"),
         rw!("inter_union"; "(Binop Inter ?a (Binop Union ?a ?b))"=> "?a"),
         rw!("inter_empty"; "(Binop Inter ?a Empty)"=> "Empty"),
+        rw!(
+            "dedup_union";
+            "(Fold Union ?list)" => {
+                let list = "?list".parse().unwrap();
+                let op = Cad::Union;
+                Dedup { list, op }
+            }
+        ),
+        rw!(
+            "dedup_inter";
+            "(Fold Inter ?list)" => {
+                let list = "?list".parse().unwrap();
+                let op = Cad::Inter;
+                Dedup { list, op }
+            }
+        ),
 
         // MapI: aka Tabulate
         rw!("repeat_mapi"; "(Repeat ?n ?x)"=> "(MapI ?n ?x)"),
@@ -858,3 +908,176 @@ impl Applier<Cad, MetaAnalysis> for Flatten {
         results
     }
 }
+
+// Drop operands of an idempotent fold (Union, Inter) that are already in the list
+#[derive(Debug)]
+struct Dedup {
+    op: Cad,
+    list: Var,
+}
+
+impl Applier<Cad, MetaAnalysis> for Dedup {
+    fn apply_one(
+        &self,
+        egraph: &mut EGraph,
+        eclass: Id,
+        map: &Subst,
+        _searcher_ast: Option<&PatternAst<Cad>>,
+        _rule_name: Symbol,
+    ) -> Vec<Id> {
+        let ids = get_meta_list!(egraph, map[self.list]).clone();
+        let unique: IndexSet<Id> = ids.iter().map(|&id| egraph.find(id)).collect();
+        if unique.len() == ids.len() {
+            return vec![];
+        }
+
+        let new_list = egraph.add(Cad::List(unique.into_iter().collect()));
+        let op = egraph.add(self.op.clone());
+        let new_fold = egraph.add(Cad::Fold([op, new_list]));
+
+        let results = vec![new_fold];
+        for result in results.iter() {
+            egraph.union(eclass, *result);
+        }
+        results
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    // rewrite `start` with the single rule named `rule` and check whether `goal` is reached
+    pub(crate) fn is_rewritten_to(rule: &str, start: &str, goal: &str) -> bool {
+        let rules: Vec<Rewrite> = cad_rules()
+            .into_iter()
+            .chain(cad_identity_rules())
+            .filter(|r| r.name.as_str() == rule)
+            .collect();
+        assert_eq!(rules.len(), 1, "no rule named {}", rule);
+        let start: RecExpr<Cad> = start.parse().unwrap();
+        let goal: RecExpr<Cad> = goal.parse().unwrap();
+        let runner: Runner<Cad, MetaAnalysis> = Runner::new(MetaAnalysis)
+            .with_iter_limit(5)
+            .with_expr(&start)
+            .run(&rules);
+        !runner.egraph.equivs(&start, &goal).is_empty()
+    }
+
+    #[test]
+    fn inter_trans() {
+        assert!(is_rewritten_to(
+            "inter_trans",
+            "(Binop Inter
+              (Affine Trans (Vec3 1 2 3) (Cube (Vec3 1 1 1) false))
+              (Affine Trans (Vec3 1 2 3) (Cube (Vec3 2 2 2) false)))",
+            "(Affine Trans (Vec3 1 2 3)
+              (Binop Inter (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+        ));
+    }
+
+    #[test]
+    fn affine_binop() {
+        assert!(is_rewritten_to(
+            "affine_binop",
+            "(Binop Union
+              (Affine Scale (Vec3 2 2 2) (Cube (Vec3 1 1 1) false))
+              (Affine Scale (Vec3 2 2 2) (Cube (Vec3 2 2 2) false)))",
+            "(Affine Scale (Vec3 2 2 2)
+              (Binop Union (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+        ));
+        assert!(is_rewritten_to(
+            "affine_binop",
+            "(Binop Inter
+              (Affine Rotate (Vec3 0 0 90) (Cube (Vec3 1 1 1) false))
+              (Affine Rotate (Vec3 0 0 90) (Cube (Vec3 2 2 2) false)))",
+            "(Affine Rotate (Vec3 0 0 90)
+              (Binop Inter (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+        ));
+    }
+
+    #[test]
+    fn affine_binop_skips_diff() {
+        assert!(!is_rewritten_to(
+            "affine_binop",
+            "(Binop Diff
+              (Affine Scale (Vec3 2 2 2) (Cube (Vec3 1 1 1) false))
+              (Affine Scale (Vec3 2 2 2) (Cube (Vec3 2 2 2) false)))",
+            "(Affine Scale (Vec3 2 2 2)
+              (Binop Diff (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+        ));
+    }
+
+    #[test]
+    fn affine_binop_skips_flattening_scale_inter() {
+        // both cubes end up on the z = 0 plane, where they overlap, even though they are disjoint
+        assert!(!is_rewritten_to(
+            "affine_binop",
+            "(Binop Inter
+              (Affine Scale (Vec3 1 1 0) (Cube (Vec3 1 1 1) false))
+              (Affine Scale (Vec3 1 1 0) (Affine Trans (Vec3 0 0 5) (Cube (Vec3 1 1 1) false))))",
+            "(Affine Scale (Vec3 1 1 0)
+              (Binop Inter
+                (Cube (Vec3 1 1 1) false)
+                (Affine Trans (Vec3 0 0 5) (Cube (Vec3 1 1 1) false))))",
+        ));
+        assert!(is_rewritten_to(
+            "affine_binop",
+            "(Binop Union
+              (Affine Scale (Vec3 1 1 0) (Cube (Vec3 1 1 1) false))
+              (Affine Scale (Vec3 1 1 0) (Affine Trans (Vec3 0 0 5) (Cube (Vec3 1 1 1) false))))",
+            "(Affine Scale (Vec3 1 1 0)
+              (Binop Union
+                (Cube (Vec3 1 1 1) false)
+                (Affine Trans (Vec3 0 0 5) (Cube (Vec3 1 1 1) false))))",
+        ));
+    }
+
+    #[test]
+    fn dedup_union() {
+        assert!(is_rewritten_to(
+            "dedup_union",
+            "(Fold Union (List
+              (Cube (Vec3 1 1 1) false)
+              (Cube (Vec3 2 2 2) false)
+              (Cube (Vec3 1 1 1) false)))",
+            "(Fold Union (List (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+        ));
+    }
+
+    #[test]
+    fn dedup_union_extracts_deduplicated_fold() {
+        let start: RecExpr<Cad> = "(Fold Union (List
+              (Cube (Vec3 1 1 1) false)
+              (Cube (Vec3 2 2 2) false)
+              (Cube (Vec3 1 1 1) false)))"
+            .parse()
+            .unwrap();
+        let runner: Runner<Cad, MetaAnalysis> = Runner::new(MetaAnalysis)
+            .with_iter_limit(5)
+            .with_expr(&start)
+            .run(&cad_rules());
+        let (_, best) = crate::cost::extract_best(&runner.egraph, runner.roots[0]);
+        assert_eq!(
+            best.to_string(),
+            "(Fold Union (List (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))"
+        );
+    }
+
+    #[test]
+    fn dedup_inter() {
+        assert!(is_rewritten_to(
+            "dedup_inter",
+            "(Fold Inter (List
+              (Cube (Vec3 1 1 1) false)
+              (Cube (Vec3 2 2 2) false)
+              (Cube (Vec3 2 2 2) false)))",
+            "(Fold Inter (List (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+        ));
+        assert!(!is_rewritten_to(
+            "dedup_inter",
+            "(Fold Inter (List (Cube (Vec3 1 1 1) false) (Cube (Vec3 2 2 2) false)))",
+            "(Fold Inter (List (Cube (Vec3 1 1 1) false)))",
+        ));
+    }
+}
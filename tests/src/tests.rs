@@ -28,9 +28,10 @@ use serde::Serialize;
 
 use egg::*;
 use rewrite::cad::{Cad, MetaAnalysis};
-use rewrite::cost::{Cost, CostFn};
+use rewrite::cost::{extract_best, Cost, CostFn};
 use rewrite::export::scad::Scad;
 use rewrite::prune::remove_empty;
+use rewrite::report::CadOptimizeReport;
 use std::default::Default;
 
 #[derive(Serialize)]
@@ -43,6 +44,7 @@ pub struct RunResult {
     pub extract_time: f64,
     pub final_scad: String,
     pub stop_reason: StopReason,
+    pub optimize_report: CadOptimizeReport,
 
     // metrics
     pub ast_size: usize,
@@ -129,17 +131,17 @@ pub fn optimize(input: &str) -> (String, RunResult) {
     const PRE_EXTRACT: bool = true;
 
     println!("input is {}", input);
-    let initial_expr: RecExpr<_> = input.parse().expect("Couldn't parse input");
+    let input_expr: RecExpr<_> = input.parse().expect("Couldn't parse input");
 
     // remove empty
-    let n = (initial_expr.as_ref().len() - 1).into();
+    let n = (input_expr.as_ref().len() - 1).into();
     let mut out = RecExpr::from(vec![]);
-    remove_empty(&initial_expr, n, &mut out).expect("input was empty");
+    remove_empty(&input_expr, n, &mut out).expect("input was empty");
     let initial_expr = out;
 
     let initial_cost = CostFn.cost_rec(&initial_expr);
 
-    let initial_expr = if PRE_EXTRACT {
+    let pre_runner = if PRE_EXTRACT {
         let pre_rules = rewrite::rules::pre_rules();
         let runner = MyRunner::new(MetaAnalysis::default())
             .with_iter_limit(ITERATIONS)
@@ -147,11 +149,13 @@ pub fn optimize(input: &str) -> (String, RunResult) {
             .with_time_limit(Duration::from_secs_f64(1.0))
             .with_expr(&initial_expr)
             .run(&pre_rules);
-        Extractor::new(&runner.egraph, CostFn)
-            .find_best(runner.roots[0])
-            .1
+        Some(runner)
     } else {
-        initial_expr
+        None
+    };
+    let initial_expr = match &pre_runner {
+        Some(runner) => extract_best(&runner.egraph, runner.roots[0]).1,
+        None => initial_expr,
     };
 
     let rules = rewrite::rules::rules();
@@ -177,11 +181,18 @@ pub fn optimize(input: &str) -> (String, RunResult) {
 
     let root = runner.roots[0];
     let extract_time = Instant::now();
-    let best = Extractor::new(&runner.egraph, CostFn).find_best(root);
+    let best = extract_best(&runner.egraph, root);
     let extract_time = extract_time.elapsed().as_secs_f64();
 
     println!("Best ({}): {}", best.0, best.1.pretty(80));
 
+    let runners: Vec<&MyRunner> = pre_runner.iter().chain(Some(&runner)).collect();
+    let optimize_report = CadOptimizeReport::new(&input_expr, &best.1, &runners);
+    println!(
+        "Nodes: {} -> {}",
+        optimize_report.input_nodes, optimize_report.output_nodes
+    );
+
     let report = RunResult {
         initial_expr: initial_expr.pretty(80),
         initial_cost,
@@ -195,6 +206,7 @@ pub fn optimize(input: &str) -> (String, RunResult) {
         ast_depth: ast_depth(&best.1),
         n_mapis: n_mapis(&best.1),
         depth_under_mapis: depth_under_mapis(&best.1),
+        optimize_report,
     };
 
     (best.1.pretty(80), report)